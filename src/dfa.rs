@@ -19,6 +19,7 @@ use std::collections::{BTreeSet, HashMap, HashSet};
 use crate::grapheme::{Grapheme, GraphemeCluster};
 use itertools::Itertools;
 use linked_list::LinkedList;
+use petgraph::algo::is_isomorphic_matching;
 use petgraph::dot::{Config, Dot};
 use petgraph::graph::NodeIndex;
 use petgraph::stable_graph::{Edges, StableGraph};
@@ -36,6 +37,7 @@ pub(crate) struct DFA {
     initial_state: State,
     final_state_indices: HashSet<usize>,
     is_output_colorized: bool,
+    forward_index: HashMap<(State, String), State>,
 }
 
 impl DFA {
@@ -48,6 +50,34 @@ impl DFA {
         dfa
     }
 
+    pub(crate) fn from_parts(
+        graph: StableGraph<StateLabel, EdgeLabel>,
+        initial_state: State,
+        final_state_indices: HashSet<usize>,
+        alphabet: BTreeSet<Grapheme>,
+        is_output_colorized: bool,
+    ) -> Self {
+        let forward_index = graph
+            .edge_indices()
+            .map(|edge| {
+                let (source, target) = graph.edge_endpoints(edge).unwrap();
+                let value = graph.edge_weight(edge).unwrap().value().to_string();
+                ((source, value), target)
+            })
+            .collect();
+
+        let mut dfa = Self {
+            alphabet,
+            graph,
+            initial_state,
+            final_state_indices,
+            is_output_colorized,
+            forward_index,
+        };
+        dfa.minimize();
+        dfa
+    }
+
     pub(crate) fn state_count(&self) -> usize {
         self.graph.node_count()
     }
@@ -69,6 +99,96 @@ impl DFA {
         self.final_state_indices.contains(&state.index())
     }
 
+    pub(crate) fn accepts(&self, input: &str) -> bool {
+        let cluster = GraphemeCluster::from(input, self.is_output_colorized);
+        let graphemes = cluster.graphemes();
+        self.accepts_from(self.initial_state, graphemes, 0)
+    }
+
+    fn accepts_from(&self, state: State, graphemes: &[Grapheme], index: usize) -> bool {
+        if index == graphemes.len() {
+            return self.is_final_state(state);
+        }
+
+        let value = graphemes[index].value();
+
+        for edge in self.outgoing_edges(state) {
+            let grapheme = edge.weight();
+
+            if grapheme.value() != value {
+                continue;
+            }
+
+            let mut run_length = 0;
+            while index + run_length < graphemes.len() && graphemes[index + run_length].value() == value {
+                run_length += 1;
+            }
+            run_length = run_length.min(grapheme.maximum());
+
+            if run_length < grapheme.minimum() {
+                continue;
+            }
+
+            for repetitions in (grapheme.minimum()..=run_length).rev() {
+                if self.accepts_from(edge.target(), graphemes, index + repetitions) {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    pub(crate) fn is_equivalent(&self, other: &DFA) -> bool {
+        if self.alphabet != other.alphabet {
+            return false;
+        }
+
+        let self_graph = self
+            .graph
+            .map(|state, _| self.is_final_state(state), |_, edge| edge.clone());
+        let other_graph = other
+            .graph
+            .map(|state, _| other.is_final_state(state), |_, edge| edge.clone());
+
+        is_isomorphic_matching(
+            &self_graph,
+            &other_graph,
+            |this_is_final, other_is_final| this_is_final == other_is_final,
+            |this_grapheme, other_grapheme| {
+                this_grapheme.value() == other_grapheme.value()
+                    && this_grapheme.minimum() == other_grapheme.minimum()
+                    && this_grapheme.maximum() == other_grapheme.maximum()
+            },
+        )
+    }
+
+    pub(crate) fn to_dot(&self) -> String {
+        format!(
+            "{}",
+            Dot::with_attr_getters(
+                &self.graph,
+                &[Config::EdgeNoLabel, Config::NodeNoLabel],
+                &|_, edge| {
+                    let grapheme = edge.weight();
+                    format!(
+                        "label = \"{} {{{},{}}}\"",
+                        grapheme.value(),
+                        grapheme.minimum(),
+                        grapheme.maximum()
+                    )
+                },
+                &|_, (state, _)| {
+                    if self.is_final_state(state) {
+                        "shape = doublecircle".to_string()
+                    } else {
+                        "shape = circle".to_string()
+                    }
+                },
+            )
+        )
+    }
+
     #[allow(dead_code)]
     fn println(&self, comment: &str) {
         println!(
@@ -88,6 +208,7 @@ impl DFA {
             initial_state,
             final_state_indices: HashSet::new(),
             is_output_colorized,
+            forward_index: HashMap::new(),
         }
     }
 
@@ -109,33 +230,33 @@ impl DFA {
     }
 
     fn find_next_state(&mut self, current_state: State, grapheme: &Grapheme) -> Option<State> {
-        for next_state in self.graph.neighbors(current_state) {
-            let edge_idx = self.graph.find_edge(current_state, next_state).unwrap();
-            let current_grapheme = self.graph.edge_weight(edge_idx).unwrap();
-
-            if current_grapheme.value() != grapheme.value() {
-                continue;
-            }
-
-            if current_grapheme.maximum() == grapheme.maximum() - 1 {
-                let min = min(current_grapheme.minimum(), grapheme.minimum());
-                let max = max(current_grapheme.maximum(), grapheme.maximum());
-                let new_grapheme =
-                    Grapheme::new(grapheme.chars().clone(), min, max, self.is_output_colorized);
-                self.graph
-                    .update_edge(current_state, next_state, new_grapheme);
-                return Some(next_state);
-            } else if current_grapheme.maximum() == grapheme.maximum() {
-                return Some(next_state);
-            }
+        let next_state = *self
+            .forward_index
+            .get(&(current_state, grapheme.value().to_string()))?;
+        let edge_idx = self.graph.find_edge(current_state, next_state).unwrap();
+        let current_grapheme = self.graph.edge_weight(edge_idx).unwrap();
+
+        if current_grapheme.maximum() == grapheme.maximum() - 1 {
+            let min = min(current_grapheme.minimum(), grapheme.minimum());
+            let max = max(current_grapheme.maximum(), grapheme.maximum());
+            let new_grapheme =
+                Grapheme::new(grapheme.chars().clone(), min, max, self.is_output_colorized);
+            self.graph
+                .update_edge(current_state, next_state, new_grapheme);
+            Some(next_state)
+        } else if current_grapheme.maximum() == grapheme.maximum() {
+            Some(next_state)
+        } else {
+            None
         }
-        None
     }
 
     fn add_new_state(&mut self, current_state: State, edge_label: &Grapheme) -> State {
         let next_state = self.graph.add_node("".to_string());
         self.graph
             .add_edge(current_state, next_state, edge_label.clone());
+        self.forward_index
+            .insert((current_state, edge_label.value().to_string()), next_state);
         next_state
     }
 
@@ -144,12 +265,13 @@ impl DFA {
         let mut p = self.get_initial_partition();
         let mut w = p.iter().cloned().collect_vec();
         let mut p_cursor = p.cursor();
+        let predecessors = self.build_predecessor_index();
 
         while !w.is_empty() {
             let a = w.drain(0..1).next().unwrap();
 
             for edge_label in self.alphabet.iter() {
-                let x = self.get_parent_states(&a, edge_label);
+                let x = Self::get_parent_states(&predecessors, &a, edge_label);
                 let mut replacements = vec![];
 
                 while let Some(y) = p_cursor.peek_next() {
@@ -205,20 +327,38 @@ impl DFA {
         linked_list![final_states, non_final_states]
     }
 
-    fn get_parent_states(&self, a: &HashSet<State>, label: &Grapheme) -> HashSet<State> {
+    fn build_predecessor_index(&self) -> HashMap<(State, String), Vec<(State, usize, usize)>> {
+        let mut predecessors: HashMap<(State, String), Vec<(State, usize, usize)>> =
+            HashMap::new();
+
+        for edge in self.graph.edge_indices() {
+            let (source, target) = self.graph.edge_endpoints(edge).unwrap();
+            let grapheme = self.graph.edge_weight(edge).unwrap();
+            predecessors
+                .entry((target, grapheme.value().to_string()))
+                .or_default()
+                .push((source, grapheme.minimum(), grapheme.maximum()));
+        }
+
+        predecessors
+    }
+
+    fn get_parent_states(
+        predecessors: &HashMap<(State, String), Vec<(State, usize, usize)>>,
+        a: &HashSet<State>,
+        label: &Grapheme,
+    ) -> HashSet<State> {
         let mut x = HashSet::new();
 
         for &state in a {
-            let direct_parent_states = self.graph.neighbors_directed(state, Direction::Incoming);
-            for parent_state in direct_parent_states {
-                let edge = self.graph.find_edge(parent_state, state).unwrap();
-                let grapheme = self.graph.edge_weight(edge).unwrap();
-                if grapheme.value() == label.value()
-                    && (grapheme.maximum() == label.maximum()
-                        || grapheme.minimum() == label.minimum())
-                {
-                    x.insert(parent_state);
-                    break;
+            if let Some(direct_parent_states) =
+                predecessors.get(&(state, label.value().to_string()))
+            {
+                for &(parent_state, parent_minimum, parent_maximum) in direct_parent_states {
+                    if parent_maximum == label.maximum() || parent_minimum == label.minimum() {
+                        x.insert(parent_state);
+                        break;
+                    }
                 }
             }
         }
@@ -262,15 +402,28 @@ impl DFA {
                 }
             }
         }
+
+        let forward_index = graph
+            .edge_indices()
+            .map(|edge| {
+                let (source, target) = graph.edge_endpoints(edge).unwrap();
+                let value = graph.edge_weight(edge).unwrap().value().to_string();
+                ((source, value), target)
+            })
+            .collect();
+
         self.initial_state = new_initial_state.unwrap();
         self.final_state_indices = final_state_indices;
         self.graph = graph;
+        self.forward_index = forward_index;
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::time::Instant;
 
     #[test]
     fn test_state_count() {
@@ -281,6 +434,43 @@ mod tests {
         assert_eq!(dfa.state_count(), 5);
     }
 
+    #[test]
+    fn test_minimize_large_input_set_with_predecessor_index() {
+        let words = (0..2_000).map(|i| format!("word{}", i)).collect_vec();
+        let clusters = words
+            .iter()
+            .map(|word| GraphemeCluster::from(word, false))
+            .collect_vec();
+
+        let dfa = DFA::from(clusters, false);
+
+        assert!(words.iter().all(|word| dfa.accepts(word)));
+    }
+
+    // Ignored by default since it's a timing measurement, not a correctness check.
+    // Run with `cargo test bench_minimize_predecessor_index -- --ignored --nocapture`.
+    // Compared against the pre-index `get_parent_states`/`find_next_state` (the
+    // parent of 618c2e3) by running this same workload there: that version
+    // re-scans the graph with `find_edge`/`edge_weight` for every parent state and
+    // every alphabet symbol, so its running time grows with the input size instead
+    // of staying roughly flat per state like the indexed version below.
+    #[test]
+    #[ignore]
+    fn bench_minimize_predecessor_index() {
+        let words = (0..5_000).map(|i| format!("word{}", i)).collect_vec();
+        let clusters = words
+            .iter()
+            .map(|word| GraphemeCluster::from(word, false))
+            .collect_vec();
+
+        let start = Instant::now();
+        let dfa = DFA::from(clusters, false);
+        let elapsed = start.elapsed();
+
+        eprintln!("minimized {} words in {:?}", words.len(), elapsed);
+        assert!(words.iter().all(|word| dfa.accepts(word)));
+    }
+
     #[test]
     fn test_is_final_state() {
         let dfa = DFA::from(vec![GraphemeCluster::from("abcd", false)], false);
@@ -395,4 +585,117 @@ mod tests {
         assert_eq!(dfa.graph.node_count(), 5);
         assert_eq!(dfa.graph.edge_count(), 5);
     }
+
+    #[test]
+    fn test_to_dot() {
+        let dfa = DFA::from(vec![GraphemeCluster::from("ab", false)], false);
+        let dot = dfa.to_dot();
+
+        assert!(dot.contains("digraph"));
+        assert!(dot.contains("a {1,1}"));
+        assert!(dot.contains("b {1,1}"));
+        assert!(dot.contains("shape = doublecircle"));
+    }
+
+    #[test]
+    fn test_is_equivalent() {
+        let dfa_1 = DFA::from(
+            vec![
+                GraphemeCluster::from("abcd", false),
+                GraphemeCluster::from("abxd", false),
+            ],
+            false,
+        );
+        let dfa_2 = DFA::from(
+            vec![
+                GraphemeCluster::from("abxd", false),
+                GraphemeCluster::from("abcd", false),
+            ],
+            false,
+        );
+        let dfa_3 = DFA::from(vec![GraphemeCluster::from("abcd", false)], false);
+
+        assert!(dfa_1.is_equivalent(&dfa_2));
+        assert!(!dfa_1.is_equivalent(&dfa_3));
+    }
+
+    #[test]
+    fn test_is_equivalent_rejects_same_topology_different_final_states() {
+        let single_final_state = DFA::from(vec![GraphemeCluster::from("ab", false)], false);
+        let intermediate_final_state = DFA::from(
+            vec![
+                GraphemeCluster::from("a", false),
+                GraphemeCluster::from("ab", false),
+            ],
+            false,
+        );
+
+        assert_eq!(single_final_state.state_count(), intermediate_final_state.state_count());
+        assert!(!single_final_state.is_equivalent(&intermediate_final_state));
+        assert!(!intermediate_final_state.is_equivalent(&single_final_state));
+    }
+
+    #[test]
+    fn test_accepts() {
+        let dfa = DFA::from(
+            vec![
+                GraphemeCluster::from("abcd", false),
+                GraphemeCluster::from("abxd", false),
+            ],
+            false,
+        );
+
+        assert!(dfa.accepts("abcd"));
+        assert!(dfa.accepts("abxd"));
+        assert!(!dfa.accepts("abc"));
+        assert!(!dfa.accepts("abcde"));
+        assert!(!dfa.accepts("xyz"));
+    }
+
+    #[quickcheck]
+    fn prop_minimization_preserves_accepted_language(words: HashSet<String>) -> bool {
+        let words = words
+            .into_iter()
+            .filter(|word| !word.is_empty())
+            .collect_vec();
+
+        if words.is_empty() {
+            return true;
+        }
+
+        let clusters = words
+            .iter()
+            .map(|word| GraphemeCluster::from(word, false))
+            .collect_vec();
+
+        let mut unminimized_dfa = DFA::new(false);
+        for cluster in clusters {
+            unminimized_dfa.insert(cluster);
+        }
+
+        let probes = words
+            .iter()
+            .map(|word| format!("{}!", word))
+            .chain(std::iter::once("".to_string()))
+            .collect_vec();
+
+        let all_inputs_accepted_before_minimization =
+            words.iter().all(|word| unminimized_dfa.accepts(word));
+
+        let mut minimized_dfa = DFA::new(false);
+        minimized_dfa.graph = unminimized_dfa.graph.clone();
+        minimized_dfa.initial_state = unminimized_dfa.initial_state;
+        minimized_dfa.final_state_indices = unminimized_dfa.final_state_indices.clone();
+        minimized_dfa.alphabet = unminimized_dfa.alphabet.clone();
+        minimized_dfa.minimize();
+
+        let words_agree = words
+            .iter()
+            .all(|word| unminimized_dfa.accepts(word) == minimized_dfa.accepts(word));
+        let probes_agree = probes
+            .iter()
+            .all(|probe| unminimized_dfa.accepts(probe) == minimized_dfa.accepts(probe));
+
+        all_inputs_accepted_before_minimization && words_agree && probes_agree
+    }
 }
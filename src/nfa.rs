@@ -0,0 +1,275 @@
+/*
+ * Copyright © 2019-2020 Peter M. Stahl pemistahl@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ * http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either expressed or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::dfa::DFA;
+use crate::grapheme::{Grapheme, GraphemeCluster};
+use itertools::Itertools;
+use petgraph::graph::NodeIndex;
+use petgraph::stable_graph::StableGraph;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+
+type State = NodeIndex<u32>;
+type StateLabel = String;
+type EdgeLabel = Option<Grapheme>;
+
+pub(crate) struct NFA {
+    graph: StableGraph<StateLabel, EdgeLabel>,
+    initial_state: State,
+    final_states: HashSet<State>,
+}
+
+impl NFA {
+    pub(crate) fn from_literal(cluster: GraphemeCluster) -> Self {
+        let mut graph = StableGraph::new();
+        let initial_state = graph.add_node("".to_string());
+        let mut current_state = initial_state;
+
+        for grapheme in cluster.graphemes() {
+            let next_state = graph.add_node("".to_string());
+            graph.add_edge(current_state, next_state, Some(grapheme.clone()));
+            current_state = next_state;
+        }
+
+        let mut final_states = HashSet::new();
+        final_states.insert(current_state);
+
+        Self {
+            graph,
+            initial_state,
+            final_states,
+        }
+    }
+
+    pub(crate) fn concat(mut self, other: NFA) -> Self {
+        let mapping = self.merge(&other);
+        let other_initial_state = *mapping.get(&other.initial_state).unwrap();
+
+        for &final_state in &self.final_states {
+            self.graph.add_edge(final_state, other_initial_state, None);
+        }
+
+        self.final_states = other
+            .final_states
+            .iter()
+            .map(|state| *mapping.get(state).unwrap())
+            .collect();
+
+        self
+    }
+
+    pub(crate) fn alternation(branches: Vec<NFA>) -> Self {
+        let mut graph = StableGraph::new();
+        let initial_state = graph.add_node("".to_string());
+        let final_state = graph.add_node("".to_string());
+        let mut final_states = HashSet::new();
+        final_states.insert(final_state);
+
+        let mut nfa = Self {
+            graph,
+            initial_state,
+            final_states,
+        };
+
+        for branch in &branches {
+            let mapping = nfa.merge(branch);
+            let branch_initial_state = *mapping.get(&branch.initial_state).unwrap();
+            nfa.graph.add_edge(initial_state, branch_initial_state, None);
+
+            for branch_final_state in &branch.final_states {
+                let mapped_final_state = *mapping.get(branch_final_state).unwrap();
+                nfa.graph.add_edge(mapped_final_state, final_state, None);
+            }
+        }
+
+        nfa
+    }
+
+    pub(crate) fn optional(mut self) -> Self {
+        for &final_state in &self.final_states.clone() {
+            self.graph.add_edge(self.initial_state, final_state, None);
+        }
+        self
+    }
+
+    pub(crate) fn to_dfa(&self, is_output_colorized: bool) -> DFA {
+        let mut alphabet = BTreeSet::new();
+        for edge in self.graph.edge_references() {
+            if let Some(grapheme) = edge.weight() {
+                alphabet.insert(grapheme.clone());
+            }
+        }
+
+        let mut dfa_graph = StableGraph::new();
+        let mut dfa_final_state_indices = HashSet::new();
+        let mut state_mappings: HashMap<BTreeSet<State>, State> = HashMap::new();
+        let mut worklist = VecDeque::new();
+
+        let mut initial_set = BTreeSet::new();
+        initial_set.insert(self.initial_state);
+        let initial_set = self.epsilon_closure(&initial_set);
+
+        let dfa_initial_state = dfa_graph.add_node("".to_string());
+        state_mappings.insert(initial_set.clone(), dfa_initial_state);
+        self.mark_if_final(&initial_set, dfa_initial_state, &mut dfa_final_state_indices);
+        worklist.push_back(initial_set);
+
+        while let Some(current_set) = worklist.pop_front() {
+            let current_dfa_state = *state_mappings.get(&current_set).unwrap();
+
+            for grapheme in alphabet.iter() {
+                let move_set = self.mv(&current_set, grapheme.value());
+                if move_set.is_empty() {
+                    continue;
+                }
+
+                let next_set = self.epsilon_closure(&move_set);
+                let next_dfa_state = match state_mappings.get(&next_set) {
+                    Some(&state) => state,
+                    None => {
+                        let new_state = dfa_graph.add_node("".to_string());
+                        state_mappings.insert(next_set.clone(), new_state);
+                        self.mark_if_final(&next_set, new_state, &mut dfa_final_state_indices);
+                        worklist.push_back(next_set);
+                        new_state
+                    }
+                };
+
+                dfa_graph.add_edge(current_dfa_state, next_dfa_state, grapheme.clone());
+            }
+        }
+
+        DFA::from_parts(
+            dfa_graph,
+            dfa_initial_state,
+            dfa_final_state_indices,
+            alphabet,
+            is_output_colorized,
+        )
+    }
+
+    fn merge(&mut self, other: &NFA) -> HashMap<State, State> {
+        let mut mapping = HashMap::new();
+
+        for old_state in other.graph.node_indices() {
+            let new_state = self.graph.add_node("".to_string());
+            mapping.insert(old_state, new_state);
+        }
+
+        for edge in other.graph.edge_references() {
+            let new_source = *mapping.get(&edge.source()).unwrap();
+            let new_target = *mapping.get(&edge.target()).unwrap();
+            self.graph
+                .add_edge(new_source, new_target, edge.weight().clone());
+        }
+
+        mapping
+    }
+
+    fn epsilon_closure(&self, states: &BTreeSet<State>) -> BTreeSet<State> {
+        let mut closure = states.clone();
+        let mut stack = states.iter().copied().collect_vec();
+
+        while let Some(state) = stack.pop() {
+            for edge in self.graph.edges_directed(state, Direction::Outgoing) {
+                if edge.weight().is_none() && closure.insert(edge.target()) {
+                    stack.push(edge.target());
+                }
+            }
+        }
+
+        closure
+    }
+
+    fn mv(&self, states: &BTreeSet<State>, value: &str) -> BTreeSet<State> {
+        let mut result = BTreeSet::new();
+
+        for &state in states {
+            for edge in self.graph.edges_directed(state, Direction::Outgoing) {
+                if let Some(grapheme) = edge.weight() {
+                    if grapheme.value() == value {
+                        result.insert(edge.target());
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    fn mark_if_final(
+        &self,
+        states: &BTreeSet<State>,
+        dfa_state: State,
+        dfa_final_state_indices: &mut HashSet<usize>,
+    ) {
+        if states.iter().any(|state| self.final_states.contains(state)) {
+            dfa_final_state_indices.insert(dfa_state.index());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal_nfa_accepts_only_the_literal() {
+        let nfa = NFA::from_literal(GraphemeCluster::from("abc", false));
+        let dfa = nfa.to_dfa(false);
+
+        assert!(dfa.accepts("abc"));
+        assert!(!dfa.accepts("ab"));
+        assert!(!dfa.accepts("abcd"));
+    }
+
+    #[test]
+    fn test_concat() {
+        let nfa = NFA::from_literal(GraphemeCluster::from("ab", false))
+            .concat(NFA::from_literal(GraphemeCluster::from("cd", false)));
+        let dfa = nfa.to_dfa(false);
+
+        assert!(dfa.accepts("abcd"));
+        assert!(!dfa.accepts("ab"));
+        assert!(!dfa.accepts("cd"));
+    }
+
+    #[test]
+    fn test_alternation() {
+        let nfa = NFA::alternation(vec![
+            NFA::from_literal(GraphemeCluster::from("cat", false)),
+            NFA::from_literal(GraphemeCluster::from("dog", false)),
+        ]);
+        let dfa = nfa.to_dfa(false);
+
+        assert!(dfa.accepts("cat"));
+        assert!(dfa.accepts("dog"));
+        assert!(!dfa.accepts("cow"));
+    }
+
+    #[test]
+    fn test_optional() {
+        let nfa = NFA::from_literal(GraphemeCluster::from("a", false))
+            .concat(NFA::from_literal(GraphemeCluster::from("b", false)).optional());
+        let dfa = nfa.to_dfa(false);
+
+        assert!(dfa.accepts("a"));
+        assert!(dfa.accepts("ab"));
+        assert!(!dfa.accepts("ac"));
+    }
+}